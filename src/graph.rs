@@ -1,85 +1,410 @@
 use std::collections::*;
-use std::fs::File;
 use std::io::*;
 use std::iter::*;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::sync::RwLock;
-use std::u64;
+use std::time::{Duration, Instant};
 
-use rayon::{Scope};
-use regex::Regex;
+use rayon::Scope;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
 
+// A packed bitset: `ceil(n / 64)` words, bit `i` set means vertex `i` is present.
+type Bitset = Vec<u64>;
+
+fn bs_set(b: &mut [u64], i: usize) {
+    b[i >> 6] |= 1u64 << (i & 63);
+}
+
+fn bs_clear(b: &mut [u64], i: usize) {
+    b[i >> 6] &= !(1u64 << (i & 63));
+}
+
+fn bs_count(b: &[u64]) -> usize {
+    b.iter().map(|w| w.count_ones() as usize).sum()
+}
+
+// Enumerate set bits, lowest first, using `trailing_zeros` over each nonzero word.
+fn bs_members(b: &[u64]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(bs_count(b));
+    for (wi, &w) in b.iter().enumerate() {
+        let mut word = w;
+        while word != 0 {
+            res.push((wi * 64) as u32 + word.trailing_zeros());
+            word &= word - 1;
+        }
+    }
+    res
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Graph {
-    adj_list: HashMap<u16, HashSet<u16>>,
+    // number of vertices after relabeling to a contiguous 0..n
+    n: usize,
+    // words per bitset row, i.e. `ceil(n / 64)`
+    words: usize,
+    // adjacency-matrix rows: bit `u` of `rows[v]` is set iff edge (v, u) exists
+    rows: Vec<Bitset>,
+    // internal index -> original vertex label. The crate's `u16` public API bounds
+    // label values to 0..=65535 and so the vertex count to at most 65536; internal indices are `u32`
+    // only to keep the search arithmetic clear of `u16` overflow.
+    labels: Vec<u16>,
 }
 
 pub struct MaxCliqueData {
-    max_clique: Vec<u16>,
-    current_clique: Vec<u16>,
+    max_clique: Vec<u32>,
+    current_clique: Vec<u32>,
+}
+
+// A snapshot handed to the progress callback.
+pub struct Progress {
+    pub nodes: u64,
+    pub best: usize,
+    pub elapsed: Duration,
+}
+
+// Callback returns `true` to ask the search to stop before spawning more branches.
+type ProgressCallback = Box<dyn Fn(Progress) -> bool + Send + Sync>;
+
+// Tuning knobs for the branch-and-bound search.
+pub struct SearchConfig {
+    // rayon worker threads; 0 uses the global pool.
+    pub threads: usize,
+    // spawn parallel tasks only while the current clique is shorter than this;
+    // deeper branches recurse sequentially in place.
+    pub split_depth: usize,
+    // invoked no more often than `report_interval` with live search statistics.
+    pub callback: Option<ProgressCallback>,
+    pub report_interval: Duration,
 }
 
-fn parse_line(reader: &mut BufReader<File>) -> Option<(u16, u16)> {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"^e (\d+) (\d+)").unwrap();
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            threads: 0,
+            split_depth: 4,
+            callback: None,
+            report_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+// Shared, lock-free-on-the-hot-path search context carried through the recursion.
+struct SearchState {
+    graph: Arc<Graph>,
+    max_clique: RwLock<Vec<u32>>,
+    best_size: AtomicUsize,
+    split_depth: usize,
+    nodes: AtomicU64,
+    stop: AtomicBool,
+    start: Instant,
+    last_report_ms: AtomicU64,
+    report_interval_ms: u64,
+    callback: Option<ProgressCallback>,
+}
+
+impl SearchState {
+    // Publish an improvement. The cheap atomic load gates the hot path lock-free;
+    // when it looks like a win we take the write lock and re-check under it so the
+    // size and the winning vector move together and can't be clobbered by a
+    // concurrent improver that raced between the size bump and the vector write.
+    fn try_improve(&self, current_clique: &[u32]) {
+        if current_clique.len() <= self.best_size.load(Ordering::Acquire) {
+            return;
+        }
+        let mut best = self.max_clique.write().unwrap();
+        if current_clique.len() > best.len() {
+            println!("New max len: {}", current_clique.len());
+            self.best_size.store(current_clique.len(), Ordering::Release);
+            *best = current_clique.to_vec();
+        }
+    }
+
+    // Fire the callback at most once per `report_interval`, using the atomic
+    // last-report timestamp as a lock-free throttle; honour a stop request.
+    fn maybe_report(&self) {
+        let callback = match &self.callback {
+            Some(cb) => cb,
+            None => return,
+        };
+        let elapsed = self.start.elapsed();
+        let now_ms = elapsed.as_millis() as u64;
+        let last = self.last_report_ms.load(Ordering::Relaxed);
+        if now_ms < last + self.report_interval_ms {
+            return;
+        }
+        if self.last_report_ms
+            .compare_exchange(last, now_ms, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+        {
+            return;
+        }
+        let progress = Progress {
+            nodes: self.nodes.load(Ordering::Relaxed),
+            best: self.best_size.load(Ordering::Relaxed),
+            elapsed,
+        };
+        if callback(progress) {
+            self.stop.store(true, Ordering::Relaxed);
+        }
     }
-    loop {
-        let mut text = String::new();
-        match reader.read_line(&mut text) {
-            Ok(size) if size > 0 => {
-                if let Some(caps) = RE.captures(&text) {
-                    return Some((caps.get(1).unwrap().as_str().parse::<u16>().unwrap(),
-                                 caps.get(2).unwrap().as_str().parse::<u16>().unwrap()));
+}
+
+// Parse a DIMACS `.clq` stream or a bare edge list. Blank and `c` comment lines are
+// skipped, a `p edge <n> <m>` header is used only to pre-size the edge buffer, and
+// edges may appear either as `e <u> <v>` or as two whitespace-separated numbers.
+fn parse_edges<R: BufRead>(reader: R) -> Result<Vec<(u16, u16)>> {
+    let mut edges = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let mut tokens = line.split_whitespace();
+        let (from, to) = match tokens.next() {
+            None => continue,
+            Some("c") => continue,
+            Some("p") => {
+                // "p edge <n> <m>": the trailing count is the number of edges.
+                if let Some(count) = tokens.last().and_then(|m| m.parse::<usize>().ok()) {
+                    edges.reserve(count);
                 }
+                continue;
             }
-            _ => return None
+            Some("e") => (tokens.next(), tokens.next()),
+            Some(first) => (Some(first), tokens.next()),
+        };
+        // Skip anything that doesn't parse as a numeric edge (DIMACS `n`/`d`/`v`
+        // descriptor lines, titles, stray tokens) instead of panicking on it.
+        if let (Some(Ok(from)), Some(Ok(to))) =
+            (from.map(str::parse::<u16>), to.map(str::parse::<u16>))
+        {
+            edges.push((from, to));
         }
     }
+    Ok(edges)
 }
 
-macro_rules! get_entry {
-    ($map:expr, $key:expr) => (*($map.entry($key).or_insert(HashSet::new())))
+// Builds a `Graph` incrementally from edges supplied in memory.
+#[derive(Default)]
+pub struct GraphBuilder {
+    edges: Vec<(u16, u16)>,
+}
+
+impl GraphBuilder {
+    pub fn new() -> Self {
+        GraphBuilder::default()
+    }
+
+    pub fn add_edge(&mut self, from: u16, to: u16) -> &mut Self {
+        self.edges.push((from, to));
+        self
+    }
+
+    pub fn build(&self) -> Arc<Graph> {
+        Arc::new(Graph::build(&self.edges))
+    }
 }
 
+// Schema tag for the on-disk graph cache. Bump the version when the `Graph` layout
+// changes so old sidecars can never be decoded into a structurally-wrong graph.
+const CACHE_SCHEMA: &[u8] = b"max_clique-graph-v1";
+
 impl Graph {
     pub fn read(filename: &str) -> Result<Arc<Self>> {
-        let mut adj_list: HashMap<u16, HashSet<u16>> = HashMap::new();
-        let file = File::open(filename)?;
-        let mut reader = BufReader::new(file);
-        while let Some((from, to)) = parse_line(&mut reader) {
-            get_entry!(adj_list, from).insert(to);
-            get_entry!(adj_list, to).insert(from);
+        let bytes = std::fs::read(filename)?;
+
+        // Key a binary cache on the SHA3-256 of the source bytes: if a sidecar for
+        // this exact content exists, deserialize the relabeled graph and skip parsing.
+        // The schema tag is folded into the hash so a layout change (bit order, field
+        // set, `words` meaning) never matches a stale cache — bump it when `Graph`
+        // changes. The sidecar directory is keyed by our real euid (not the spoofable,
+        // sometimes-unset `USER` env var) and its ownership is checked before every use,
+        // so another uid can't pre-plant a `{hash}.bin` and feed us a poisoned graph; if
+        // we can't establish a safe cache location we just skip the cache and reparse.
+        let mut hasher = Sha3_256::new();
+        hasher.update(CACHE_SCHEMA);
+        hasher.update(&bytes);
+        let hash: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+
+        if let Some(cache) = Self::cache_path(&hash) {
+            if let Ok(data) = std::fs::read(&cache) {
+                if let Ok(graph) = bincode::deserialize::<Graph>(&data) {
+                    if graph.is_well_formed() {
+                        return Ok(Arc::new(graph));
+                    }
+                }
+            }
+
+            let edges = parse_edges(&bytes[..])?;
+            let graph = Self::build(&edges);
+            if let Ok(data) = bincode::serialize(&graph) {
+                let _ = std::fs::write(&cache, data);
+            }
+            return Ok(Arc::new(graph));
+        }
+
+        let edges = parse_edges(&bytes[..])?;
+        Ok(Arc::new(Self::build(&edges)))
+    }
+
+    // Structural sanity check for a deserialized sidecar: a crafted `n`/`words`/`rows`/
+    // `labels` mismatch would otherwise feed `get_max_clique` an inconsistent bitset
+    // width, and a stray padding bit past vertex `n` would surface as an out-of-bounds
+    // `rows`/`labels` index once `bs_members` enumerates it.
+    fn is_well_formed(&self) -> bool {
+        self.rows.len() == self.n
+            && self.words == self.n.div_ceil(64)
+            && self.rows.iter().all(|row| {
+                row.len() == self.words && bs_members(row).iter().all(|&v| (v as usize) < self.n)
+            })
+            && self.labels.len() == self.n
+    }
+
+    // Locate this euid's cache directory, verifying we (and only we) own it. Returns
+    // `None` — meaning "skip the cache entirely, reparse" — whenever that can't be
+    // established safely, rather than ever falling back to a shared location.
+    #[cfg(unix)]
+    fn cache_path(hash: &str) -> Option<std::path::PathBuf> {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        // SAFETY: `geteuid` takes no arguments and always succeeds.
+        let uid = unsafe { libc::geteuid() };
+        let dir = std::env::temp_dir().join(format!("max_clique_cache_{}", uid));
+
+        std::fs::create_dir_all(&dir).ok()?;
+        if std::fs::metadata(&dir).ok()?.uid() != uid {
+            return None;
         }
-        Ok(Arc::new(Graph { adj_list, }))
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).ok()?;
+        Some(dir.join(format!("{}.bin", hash)))
+    }
+
+    #[cfg(not(unix))]
+    fn cache_path(_hash: &str) -> Option<std::path::PathBuf> {
+        None
     }
 
-    fn degree(&self, node: u16) -> u16 {
-        self.neighbours(node).len() as u16
+    // Read a graph from any `Read` source (a file, stdin, an in-memory buffer, ...).
+    pub fn from_reader<R: Read>(source: R) -> Result<Arc<Self>> {
+        let edges = parse_edges(BufReader::new(source))?;
+        Ok(Arc::new(Self::build(&edges)))
     }
 
-    fn neighbours(&self, node: u16) -> &HashSet<u16> {
-        &self.adj_list[&node]
+    // Build a graph directly from edges held in memory.
+    pub fn from_edges<I: IntoIterator<Item = (u16, u16)>>(edges: I) -> Arc<Self> {
+        Arc::new(Self::build(&edges.into_iter().collect::<Vec<_>>()))
     }
 
-    fn subgraph_neighbours<'i>(&'i self, subgraph: &'i HashSet<u16>, node: u16) -> impl Iterator<Item=&'i u16> {
-        self.neighbours(node).intersection(subgraph)
+    pub fn builder() -> GraphBuilder {
+        GraphBuilder::new()
     }
 
-    fn clique_heuristic(&self, data: &mut MaxCliqueData, mut vertexes: HashSet<u16>) {
-        if vertexes.is_empty() {
+    // Relabel the sparse `(u16, u16)` edges onto contiguous indices and pack the
+    // adjacency matrix into one bitset row per vertex.
+    fn build(edges: &[(u16, u16)]) -> Graph {
+        let mut seen: BTreeSet<u16> = BTreeSet::new();
+        for &(a, b) in edges {
+            seen.insert(a);
+            seen.insert(b);
+        }
+        let labels: Vec<u16> = seen.into_iter().collect();
+        let index: HashMap<u16, u32> = labels.iter().enumerate()
+            .map(|(i, &l)| (l, i as u32)).collect();
+        let n = labels.len();
+        let words = n.div_ceil(64);
+        let mut rows = vec![vec![0u64; words]; n];
+        for &(a, b) in edges {
+            let from = index[&a] as usize;
+            let to = index[&b] as usize;
+            if from != to {
+                bs_set(&mut rows[from], to);
+                bs_set(&mut rows[to], from);
+            }
+        }
+        Graph { n, words, rows, labels }
+    }
+
+    // A bitset with every vertex present.
+    fn all_vertices(&self) -> Bitset {
+        let mut b = vec![!0u64; self.words];
+        for i in self.n..self.words * 64 {
+            bs_clear(&mut b, i);
+        }
+        b
+    }
+
+    // Iteratively drop every vertex whose induced degree is below `lower_bound - 1`
+    // (a k-core-style peel), recomputing degrees over the shrinking set until stable.
+    fn induced_peel(&self, mut active: Bitset, lower_bound: usize) -> Bitset {
+        if lower_bound < 2 {
+            return active;
+        }
+        let threshold = lower_bound - 1;
+        loop {
+            let mut changed = false;
+            for v in bs_members(&active) {
+                if bs_count(&self.subgraph_neighbours(&active, v)) < threshold {
+                    bs_clear(&mut active, v as usize);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        active
+    }
+
+    // Split the induced subgraph over `active` into its connected components via an
+    // iterative DFS. A maximum clique lives entirely within one of them.
+    fn components(&self, active: &[u64]) -> Vec<Bitset> {
+        let mut remaining = active.to_vec();
+        let mut res = Vec::new();
+        while bs_count(&remaining) > 0 {
+            let start = bs_members(&remaining)[0];
+            let mut comp = vec![0u64; self.words];
+            bs_clear(&mut remaining, start as usize);
+            bs_set(&mut comp, start as usize);
+            let mut stack = vec![start];
+            while let Some(v) = stack.pop() {
+                for u in bs_members(&self.subgraph_neighbours(&remaining, v)) {
+                    bs_clear(&mut remaining, u as usize);
+                    bs_set(&mut comp, u as usize);
+                    stack.push(u);
+                }
+            }
+            res.push(comp);
+        }
+        res
+    }
+
+    fn degree(&self, node: u32) -> u16 {
+        self.rows[node as usize].iter().map(|w| w.count_ones()).sum::<u32>() as u16
+    }
+
+    // `rows[node] & subgraph`, word by word.
+    fn subgraph_neighbours(&self, subgraph: &[u64], node: u32) -> Bitset {
+        self.rows[node as usize].iter().zip(subgraph).map(|(a, b)| a & b).collect()
+    }
+
+    fn clique_heuristic(&self, data: &mut MaxCliqueData, vertexes: Bitset) {
+        if bs_count(&vertexes) == 0 {
             if data.current_clique.len() > data.max_clique.len() {
                 data.max_clique = data.current_clique.clone();
             }
             return
         }
 
-        let best_vertex = vertexes.iter().copied().max_by_key(
-            |v| self.subgraph_neighbours(&vertexes, *v).count()).unwrap();
+        let best_vertex = bs_members(&vertexes).into_iter().max_by_key(
+            |&v| bs_count(&self.subgraph_neighbours(&vertexes, v))).unwrap();
 
-        let neighbours = HashSet::from_iter(
-            self.subgraph_neighbours(&vertexes, best_vertex).copied()
-                .filter(|n| self.degree(*n) >= data.max_clique.len() as u16));
+        let mut neighbours = self.subgraph_neighbours(&vertexes, best_vertex);
+        let min_deg = data.max_clique.len() as u16;
+        for n in bs_members(&neighbours) {
+            if self.degree(n) < min_deg {
+                bs_clear(&mut neighbours, n as usize);
+            }
+        }
 
-        vertexes.remove(&best_vertex);
         data.current_clique.push(best_vertex);
         self.clique_heuristic(data, neighbours);
         data.current_clique.pop();
@@ -87,28 +412,28 @@ impl Graph {
 
     fn max_clique_heuristic(&self, data: &mut MaxCliqueData) {
         let mut queue = BinaryHeap::from_iter(
-            self.adj_list.keys().copied().map(|n| (self.degree(n), n)));
+            (0..self.n as u32).map(|n| (self.degree(n), n)));
 
         while let Some((_, node)) = queue.pop() {
             if self.degree(node) > data.max_clique.len() as u16 {
                 data.current_clique.push(node);
-                self.clique_heuristic(data, HashSet::from_iter(
-                    self.neighbours(node).iter().copied()
-                        .filter(|n| self.degree(*n) > data.max_clique.len() as u16)
-                ));
+                let mut neighbours = self.rows[node as usize].clone();
+                for n in bs_members(&neighbours) {
+                    if self.degree(n) <= data.max_clique.len() as u16 {
+                        bs_clear(&mut neighbours, n as usize);
+                    }
+                }
+                self.clique_heuristic(data, neighbours);
                 data.current_clique.pop();
             }
         }
     }
 
-    fn greedy_coloring(&self, vertexes: &HashSet<u16>) -> HashMap<u16, i16> {
-        let mut res = HashMap::new();
-
+    fn greedy_coloring(&self, vertexes: &[u64]) -> Vec<(u32, i16)> {
         let mut powers = Vec::from_iter(
-            vertexes.iter().copied()
-                .map(|v| (v, Vec::from_iter(self.subgraph_neighbours(vertexes, v))))
-        );
-        powers.sort_unstable_by_key(|(_, v)| -(v.len() as i32));
+            bs_members(vertexes).into_iter()
+                .map(|v| (v, self.subgraph_neighbours(vertexes, v))));
+        powers.sort_unstable_by_key(|(_, nb)| -(bs_count(nb) as i32));
         // works up to 1024 elements.
         // we wouldn't have move as if it will take too much time
         let mut used = [0; 16];
@@ -118,77 +443,203 @@ impl Graph {
         };
 
         let min_col = |arr: &[u64]| {
-            for i in 0..16 {
-                if arr[i] != !0u64 {
-                    return (64 * i + (!arr[i]).trailing_zeros() as usize) as i16;
+            for (i, &word) in arr.iter().enumerate() {
+                if word != !0u64 {
+                    return (64 * i + (!word).trailing_zeros() as usize) as i16;
                 }
             }
             unreachable!()
         };
-        for (node, neighbours) in powers {
-            for neighbour in neighbours {
-                if let Some(&val) = res.get(neighbour) {
+
+        let mut colors: HashMap<u32, i16> = HashMap::new();
+        let mut res = Vec::with_capacity(powers.len());
+        for (node, neighbours) in &powers {
+            for neighbour in bs_members(neighbours) {
+                if let Some(&val) = colors.get(&neighbour) {
                     use_col(&mut used, val);
                 }
             }
-            res.insert(node, min_col(&used));
+            let col = min_col(&used);
+            colors.insert(*node, col);
+            res.push((*node, col));
             used = [0; 16];
         }
         res
     }
 }
 
-fn max_clique_impl(graph: Arc<Graph>, max_clique: Arc<RwLock<Vec<u16>>>,
-                   current_clique: &mut Vec<u16>,
-                   mut vertexes: HashSet<u16>,
-                   s: &Scope) {
-    {
-        let len = max_clique.read().unwrap().len();
-        if current_clique.len() > len {
-            println!("New max len: {}", len);
-            *max_clique.write().unwrap() = current_clique.clone();
+// Sequential branch-and-bound below the parallel cutoff: recurses on an in-place
+// push/pop stack instead of cloning the current clique per candidate.
+fn max_clique_seq(state: &SearchState, current_clique: &mut Vec<u32>, mut vertexes: Bitset) {
+    state.nodes.fetch_add(1, Ordering::Relaxed);
+    state.maybe_report();
+    if state.stop.load(Ordering::Relaxed) {
+        return;
+    }
+    state.try_improve(current_clique);
+
+    let mut candidates = state.graph.greedy_coloring(&vertexes);
+    candidates.sort_unstable_by_key(|(_, c)| -c);
+    for (v, c) in candidates {
+        if current_clique.len() + (c as usize) < state.best_size.load(Ordering::Relaxed) {
+            return;
         }
+
+        bs_clear(&mut vertexes, v as usize);
+        let neighbours = state.graph.subgraph_neighbours(&vertexes, v);
+
+        current_clique.push(v);
+        max_clique_seq(state, current_clique, neighbours);
+        current_clique.pop();
     }
+}
 
-    let coloring = graph.greedy_coloring(&vertexes);
-    let mut candidates = Vec::from_iter(coloring.iter());
+fn max_clique_impl(state: &Arc<SearchState>, current_clique: &mut Vec<u32>,
+                   mut vertexes: Bitset, s: &Scope) {
+    state.nodes.fetch_add(1, Ordering::Relaxed);
+    state.maybe_report();
+    if state.stop.load(Ordering::Relaxed) {
+        return;
+    }
+    state.try_improve(current_clique);
 
-    candidates.sort_unstable_by_key(|(_, &c)| -c);
-    for (&v, &c) in candidates {
-        {
-            if current_clique.len() + c as usize + 1 <= max_clique.read().unwrap().len() {
-                return;
-            }
+    let mut candidates = state.graph.greedy_coloring(&vertexes);
+
+    candidates.sort_unstable_by_key(|(_, c)| -c);
+    for (v, c) in candidates {
+        if current_clique.len() + (c as usize) < state.best_size.load(Ordering::Relaxed) {
+            return;
+        }
+        if state.stop.load(Ordering::Relaxed) {
+            return;
         }
 
-        vertexes.remove(&v);
-        let neighbours = HashSet::from_iter(
-            graph.subgraph_neighbours(&vertexes, v).copied());
+        bs_clear(&mut vertexes, v as usize);
+        let neighbours = state.graph.subgraph_neighbours(&vertexes, v);
 
-        // TODO use persistent stack
-        let mut cur_clique = current_clique.clone();
-        let g = graph.clone();
-        let mc = max_clique.clone();
+        if current_clique.len() < state.split_depth {
+            let mut cur_clique = current_clique.clone();
+            let st = state.clone();
+
+            s.spawn(move |sc| {
+                cur_clique.push(v);
+                max_clique_impl(&st, &mut cur_clique, neighbours, sc);
+                cur_clique.pop();
+            });
+        } else {
+            current_clique.push(v);
+            max_clique_seq(state, current_clique, neighbours);
+            current_clique.pop();
+        }
+    }
+}
 
+fn spawn_components(s: &Scope, state: &Arc<SearchState>, active: &[u64]) {
+    for comp in state.graph.components(active) {
+        if bs_count(&comp) <= state.best_size.load(Ordering::Relaxed) {
+            continue;
+        }
+        let st = state.clone();
         s.spawn(move |sc| {
-            cur_clique.push(v);
-            max_clique_impl(g, mc, &mut cur_clique, neighbours, sc);
-            cur_clique.pop();
+            max_clique_impl(&st, &mut vec!(), comp, sc);
         });
     }
 }
 
 pub fn get_max_clique(graph: Arc<Graph>) -> Vec<u16> {
+    get_max_clique_with(graph, SearchConfig::default())
+}
+
+pub fn get_max_clique_with(graph: Arc<Graph>, config: SearchConfig) -> Vec<u16> {
     let mut data = MaxCliqueData { max_clique: vec!(), current_clique: vec!() };
     graph.max_clique_heuristic(&mut data);
     println!("Heuristic best: {}", data.max_clique.len());
-    let max_clique = Arc::new(RwLock::new(data.max_clique));
+    let lower_bound = data.max_clique.len();
+
+    // Peel low-degree vertices against the heuristic bound, then solve each surviving
+    // connected component independently and keep the largest clique.
+    let active = graph.induced_peel(graph.all_vertices(), lower_bound);
 
-    rayon::scope(|s| {
-        max_clique_impl(graph.clone(), max_clique.clone(), &mut vec!(),
-                        HashSet::from_iter(graph.adj_list.keys().cloned()), s);
+    let threads = config.threads;
+    let state = Arc::new(SearchState {
+        graph: graph.clone(),
+        max_clique: RwLock::new(data.max_clique),
+        best_size: AtomicUsize::new(lower_bound),
+        split_depth: config.split_depth,
+        nodes: AtomicU64::new(0),
+        stop: AtomicBool::new(false),
+        start: Instant::now(),
+        last_report_ms: AtomicU64::new(0),
+        report_interval_ms: config.report_interval.as_millis() as u64,
+        callback: config.callback,
     });
 
-    let res = max_clique.read().unwrap();
-    res.clone()
+    if threads > 0 {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        pool.scope(|s| spawn_components(s, &state, &active));
+    } else {
+        rayon::scope(|s| spawn_components(s, &state, &active));
+    }
+
+    let res = state.max_clique.read().unwrap();
+    res.iter().map(|&v| graph.labels[v as usize]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn structurally_eq(a: &Graph, b: &Graph) -> bool {
+        a.n == b.n && a.words == b.words && a.rows == b.rows && a.labels == b.labels
+    }
+
+    fn is_clique(graph: &Graph, clique: &[u16]) -> bool {
+        let idx: Vec<usize> = clique.iter()
+            .map(|l| graph.labels.iter().position(|x| x == l).unwrap())
+            .collect();
+        for (i, &a) in idx.iter().enumerate() {
+            for &b in &idx[i + 1..] {
+                if graph.rows[a][b >> 6] >> (b & 63) & 1 == 0 {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    #[test]
+    fn finds_known_max_clique() {
+        // K4 on {1,2,3,4} with a pendant edge 1-5; the maximum clique is {1,2,3,4}.
+        let graph = Graph::from_edges(vec![
+            (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4), (1, 5),
+        ]);
+        let clique = get_max_clique(graph.clone());
+        assert_eq!(clique.len(), 4);
+        assert!(is_clique(&graph, &clique));
+    }
+
+    #[test]
+    fn parsing_variants_agree() {
+        // The same graph expressed three ways must build identical structures.
+        let dimacs = "c a triangle with a tail\np edge 4 4\ne 1 2\ne 1 3\ne 2 3\ne 3 4\n";
+        let bare = "1 2\n1 3\n2 3\n3 4\n";
+        let prefixed = "e 1 2\ne 1 3\ne 2 3\ne 3 4\n";
+
+        let g1 = Graph::from_reader(dimacs.as_bytes()).unwrap();
+        let g2 = Graph::from_reader(bare.as_bytes()).unwrap();
+        let g3 = Graph::from_reader(prefixed.as_bytes()).unwrap();
+
+        assert!(structurally_eq(&g1, &g2));
+        assert!(structurally_eq(&g1, &g3));
+    }
+
+    #[test]
+    fn builder_matches_from_edges() {
+        let mut builder = Graph::builder();
+        builder.add_edge(1, 2).add_edge(2, 3).add_edge(1, 3);
+        let built = builder.build();
+        let direct = Graph::from_edges(vec![(1, 2), (2, 3), (1, 3)]);
+        assert!(structurally_eq(&built, &direct));
+        assert_eq!(get_max_clique(built).len(), 3);
+    }
 }
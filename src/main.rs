@@ -1,11 +1,6 @@
-#[macro_use]
-extern crate lazy_static;
-extern crate regex;
-extern crate time;
-
 use std::io::Result;
 
-mod graph;
+use max_clique::graph;
 
 fn print_clique(v: &Vec<u16>) {
     for &n in v {
@@ -15,10 +10,14 @@ fn print_clique(v: &Vec<u16>) {
 
 fn main() -> Result<()> {
     use graph::Graph;
+    use std::io::stdin;
     use time::PreciseTime;
 
-    // yes, hardcoded string here
-    let graph = Graph::read(r"C:\Users\artem\Downloads\brock400_2.clq.txt")?;
+    // Read the instance from the path given on the command line, or stdin otherwise.
+    let graph = match std::env::args().nth(1) {
+        Some(path) => Graph::read(&path)?,
+        None => Graph::from_reader(stdin().lock())?,
+    };
 
     let start = PreciseTime::now();
     let max_clique = graph::get_max_clique(graph.clone());